@@ -45,6 +45,24 @@
   [[ "$output" =~ "Signed-off-by: James Holden <jholden@rocinante.com>" ]]
 }
 
+@test "no rotation without a new commit" {
+  git-together with jh nn
+  touch foo
+  git add foo
+  git-together commit --dry-run -m "add foo"
+
+  git-together commit -m "add foo"
+  run git show --no-patch --format="%aN <%aE>"
+  [ "$output" = "James Holden <jholden@rocinante.com>" ]
+
+  git-together commit -m "add nothing"
+  touch bar
+  git add bar
+  git-together commit -m "add bar"
+  run git show --no-patch --format="%aN <%aE>"
+  [ "$output" = "Naomi Nagata <nnagata@rocinante.com>" ]
+}
+
 @test "mobbing" {
   git-together with jh nn ca
 
@@ -130,18 +148,46 @@ AUTHORS
   [ "$output" = "jh" ]
 }
 
+@test "signoff follows the committer slot" {
+  git-together with jh nn ca
+  git config git-together.committer-slot 2
+  touch foo
+  git add foo
+  git-together commit -m "add foo"
+
+  run git show --no-patch --format="%cN <%cE>"
+  [ "$output" = "Chrisjen Avasarala <avasarala@un.gov>" ]
+  run git show --no-patch --format=%B
+  [[ "$output" =~ "Signed-off-by: Chrisjen Avasarala <avasarala@un.gov>" ]]
+  [[ ! "$output" =~ "Signed-off-by: Naomi Nagata" ]]
+}
+
 @test "no signoff" {
   git-together with jh nn
   touch foo
   git add foo
   GIT_TOGETHER_NO_SIGNOFF=1 git-together commit -m "add foo"
 
+  # Nothing is attributed, so git's configured user (set by `with`) commits.
   run git show --no-patch --format="%aN <%aE>"
   [ "$output" = "James Holden <jholden@rocinante.com>" ]
+  run git show --no-patch --format="%cN <%cE>"
+  [ "$output" = "James Holden <jholden@rocinante.com>" ]
+  run git show --no-patch --format=%B
+  [[ ! "$output" =~ "Signed-off-by:" ]]
+  [[ ! "$output" =~ "Co-authored-by:" ]]
+}
+
+@test "no signoff is off for false-like values" {
+  git-together with jh nn
+  touch foo
+  git add foo
+  GIT_TOGETHER_NO_SIGNOFF=0 git-together commit -m "add foo"
+
   run git show --no-patch --format="%cN <%cE>"
   [ "$output" = "Naomi Nagata <nnagata@rocinante.com>" ]
   run git show --no-patch --format=%B
-  [[ ! "$output" =~ "Signed-off-by: Naomi Nagata <nnagata@rocinante.com>" ]]
+  [[ "$output" =~ "Signed-off-by: Naomi Nagata <nnagata@rocinante.com>" ]]
 }
 
 @test "merging" {
@@ -160,12 +206,31 @@ AUTHORS
 
   run git show --no-patch --format="%aN <%aE>"
   [ "$output" = "James Holden <jholden@rocinante.com>" ]
+  # The committer is git's user, which `git with` set to the first author.
   run git show --no-patch --format="%cN <%cE>"
-  [ "$output" = "Naomi Nagata <nnagata@rocinante.com>" ]
+  [ "$output" = "James Holden <jholden@rocinante.com>" ]
   run git show --no-patch --format=%B
   [[ ! "$output" =~ "Signed-off-by:" ]]
 }
 
+@test "merging with a message" {
+  git-together with jh nn
+  touch foo
+  git add foo
+  git-together commit -m "add foo"
+
+  git checkout -b bar
+  touch bar
+  git add bar
+  git-together commit -m "add bar"
+
+  git checkout -
+  git-together merge --no-ff -m "merge bar" bar
+
+  run git show --no-patch --format=%B
+  [[ "$output" =~ "Co-authored-by: Naomi Nagata <nnagata@rocinante.com>" ]]
+}
+
 @test "reverting" {
   git-together with jh nn
   touch foo
@@ -1,19 +1,53 @@
 use std::fmt;
+use std::str::FromStr;
 
 use errors::*;
+use md5;
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct Author {
     pub name: String,
     pub email: String,
+    /// Where the author's commit dates are stamped, from
+    /// `authors.<init>.tz`. Leaves the date to git when unset.
+    pub tz: Option<String>,
+    /// Cleared by `authors.<init>.inactive` for someone on leave, which keeps
+    /// them out of random picks but not out of explicit use.
+    pub active: bool,
+    /// The GPG key in `authors.<init>.signing-key`, for signing the commits
+    /// this author makes.
+    pub signing_key: Option<String>,
+    /// Cleared by `authors.<init>.no-trailer` for someone who'd rather not be
+    /// named in `Co-authored-by` trailers.
+    pub include_trailer: bool,
 }
 
+impl Default for Author {
+    fn default() -> Author {
+        Author {
+            name: String::new(),
+            email: String::new(),
+            tz: None,
+            active: true,
+            signing_key: None,
+            include_trailer: true,
+        }
+    }
+}
+
+const EMAIL_PLACEHOLDERS: &'static [&'static str] = &["seed", "first", "last", "domain"];
+
+#[derive(Default)]
 pub struct AuthorParser {
     pub domain: Option<String>,
+    /// How a bare email seed is expanded, using the `{seed}`, `{first}`,
+    /// `{last}`, and `{domain}` placeholders. Defaults to `{seed}@{domain}`.
+    pub email_template: Option<String>,
 }
 
 impl AuthorParser {
     pub fn parse(&self, raw: &str) -> Result<Author> {
+        let raw = strip_comment(raw);
         let mut split = raw.split(';').map(str::trim);
 
         let name = match split.next() {
@@ -33,20 +67,174 @@ impl AuthorParser {
         let email = if email_seed.contains('@') {
             email_seed.into()
         } else {
-            let domain = match self.domain {
-                Some(ref domain) => domain,
-                None => {
-                    return Err("missing domain".into());
-                }
-            };
-            format!("{}@{}", email_seed, domain)
+            self.expand_email(name, email_seed)?
         };
 
         Ok(Author {
                name: name.into(),
                email: email,
+               tz: None,
+               active: true,
+               signing_key: None,
+               include_trailer: true,
+           })
+    }
+
+    fn expand_email(&self, name: &str, seed: &str) -> Result<String> {
+        let template = self.email_template
+            .as_ref()
+            .map(String::as_str)
+            .unwrap_or("{seed}@{domain}");
+
+        let mut email = String::new();
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            email.push_str(&rest[..start]);
+            let end = rest[start..]
+                .find('}')
+                .ok_or_else(|| format!("unclosed placeholder in email template: '{}'", template))?;
+            let placeholder = &rest[start + 1..start + end];
+            email.push_str(&self.placeholder(placeholder, name, seed, template)?);
+            rest = &rest[start + end + 1..];
+        }
+        email.push_str(rest);
+
+        Ok(email)
+    }
+
+    fn placeholder(&self, placeholder: &str, name: &str, seed: &str, template: &str) -> Result<String> {
+        if !EMAIL_PLACEHOLDERS.contains(&placeholder) {
+            return Err(format!("unknown placeholder '{{{}}}' in email template: '{}'",
+                               placeholder,
+                               template)
+                               .into());
+        }
+
+        let mut words = name.split_whitespace().map(str::to_lowercase);
+        Ok(match placeholder {
+               "seed" => seed.into(),
+               "first" => words.next().unwrap_or_default(),
+               "last" => words.last().unwrap_or_default(),
+               _ => {
+                   match self.domain {
+                       Some(ref domain) => domain.clone(),
+                       None => {
+                           return Err("missing domain".into());
+                       }
+                   }
+               }
+           })
+    }
+}
+
+/// Removes the trailing comment from a raw author entry. A `#` at the start
+/// of the value or after whitespace begins one; `\#` is a literal `#`, as is
+/// one in the middle of a word.
+pub fn strip_comment(raw: &str) -> String {
+    let mut stripped = String::new();
+    let mut chars = raw.chars().peekable();
+    let mut prev: Option<char> = None;
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&'#') => {
+                stripped.push('#');
+                chars.next();
+                prev = Some('#');
+                continue;
+            }
+            '#' if prev.map_or(true, char::is_whitespace) => break,
+            _ => stripped.push(c),
+        }
+        prev = Some(c);
+    }
+    stripped.trim().into()
+}
+
+impl Author {
+    /// Parses an identity in the `Name <email>` form that `Display` writes.
+    pub fn from_identity(raw: &str) -> Result<Author> {
+        let raw = raw.trim();
+        let (name, email) = match (raw.rfind('<'), raw.ends_with('>')) {
+            (Some(start), true) => (raw[..start].trim(), raw[start + 1..raw.len() - 1].trim()),
+            _ => {
+                return Err("expected 'Name <email>'".into());
+            }
+        };
+        if name.is_empty() || name.contains('<') || name.contains('>') {
+            return Err("missing name".into());
+        }
+        if email.is_empty() || email.contains('<') {
+            return Err("missing email".into());
+        }
+
+        Ok(Author {
+               name: name.into(),
+               email: email.into(),
+               tz: None,
+               active: true,
+               signing_key: None,
+               include_trailer: true,
            })
     }
+
+    /// The author's Gravatar image, optionally at `size` pixels square and
+    /// with the `default` Gravatar falls back to (`identicon`, a URL, ...).
+    pub fn gravatar_url(&self, default: Option<&str>, size: Option<u32>) -> String {
+        let hash = md5::hex_digest(self.email.trim().to_lowercase().as_bytes());
+        let mut params = Vec::new();
+        if let Some(size) = size {
+            params.push(format!("s={}", size));
+        }
+        if let Some(default) = default {
+            let encoded: String = default
+                .bytes()
+                .map(|b| if b.is_ascii_alphanumeric() || b"-_.~".contains(&b) {
+                         (b as char).to_string()
+                     } else {
+                         format!("%{:02X}", b)
+                     })
+                .collect();
+            params.push(format!("d={}", encoded));
+        }
+
+        if params.is_empty() {
+            format!("https://www.gravatar.com/avatar/{}", hash)
+        } else {
+            format!("https://www.gravatar.com/avatar/{}?{}", hash, params.join("&"))
+        }
+    }
+
+    /// The form authors are stored in, which `AuthorParser::parse` reads back
+    /// into the same `Author`.
+    pub fn to_config_string(&self) -> String {
+        format!("{}; {}",
+                self.name.replace('#', "\\#"),
+                self.email.replace('#', "\\#"))
+    }
+}
+
+/// Reads the `Co-authored-by` trailers back out of a commit message. See
+/// `parse_trailers`.
+pub fn parse_coauthors(message: &str) -> Vec<Author> {
+    parse_trailers(message, "Co-authored-by")
+}
+
+/// Reads the `key: Name <email>` trailers in a commit message, ignoring case
+/// in the key and whitespace around it. Other lines, and trailers whose value
+/// isn't a `Name <email>` identity, are skipped.
+pub fn parse_trailers(message: &str, key: &str) -> Vec<Author> {
+    message
+        .lines()
+        .filter_map(|line| {
+            let mut split = line.splitn(2, ':');
+            match (split.next(), split.next()) {
+                (Some(k), Some(value)) if k.trim().eq_ignore_ascii_case(key) => {
+                    Author::from_identity(value).ok()
+                }
+                _ => None,
+            }
+        })
+        .collect()
 }
 
 impl fmt::Display for Author {
@@ -55,13 +243,26 @@ impl fmt::Display for Author {
     }
 }
 
+/// Parses a stored author with a full email address. Use `AuthorParser` when
+/// a domain is needed to expand an email seed.
+impl FromStr for Author {
+    type Err = Error;
+
+    fn from_str(raw: &str) -> Result<Author> {
+        AuthorParser::default().parse(raw)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
     fn new() {
-        let author_parser = AuthorParser { domain: Some("example.com".into()) };
+        let author_parser = AuthorParser {
+            domain: Some("example.com".into()),
+            ..Default::default()
+        };
 
         let author = author_parser.parse("Jane Doe; jdoe").unwrap();
         assert_eq!(author.name, "Jane Doe");
@@ -82,4 +283,139 @@ mod tests {
         assert_eq!(author.name, "Jane Doe");
         assert_eq!(author.email, "jane.doe@example.edu");
     }
+
+    #[test]
+    fn email_template() {
+        let mut author_parser = AuthorParser {
+            domain: Some("rocinante.com".into()),
+            email_template: Some("{seed}@{domain}".into()),
+        };
+        let author = author_parser.parse("James Holden; jholden").unwrap();
+        assert_eq!(author.email, "jholden@rocinante.com");
+
+        author_parser.email_template = Some("{first}.{last}@{domain}".into());
+        let author = author_parser.parse("James Holden; jholden").unwrap();
+        assert_eq!(author.email, "james.holden@rocinante.com");
+
+        author_parser.email_template = Some("{last}+{seed}@mars.mil".into());
+        let author = author_parser.parse("Bobbie Roberta Draper; bobbie").unwrap();
+        assert_eq!(author.email, "draper+bobbie@mars.mil");
+
+        let author = author_parser.parse("Bobbie Draper; bdraper@un.gov").unwrap();
+        assert_eq!(author.email, "bdraper@un.gov");
+
+        author_parser.email_template = Some("{first}.{middle}@{domain}".into());
+        assert!(author_parser.parse("James Holden; jholden").is_err());
+
+        author_parser.email_template = Some("{first@{domain}".into());
+        assert!(author_parser.parse("James Holden; jholden").is_err());
+    }
+
+    #[test]
+    fn trailing_comment() {
+        let author_parser = AuthorParser {
+            domain: Some("rocinante.com".into()),
+            ..Default::default()
+        };
+
+        let author = author_parser.parse("James Holden; jholden # lead").unwrap();
+        assert_eq!(author.name, "James Holden");
+        assert_eq!(author.email, "jholden@rocinante.com");
+
+        let author = author_parser.parse("C# Dev; cdev").unwrap();
+        assert_eq!(author.name, "C# Dev");
+
+        let author = author_parser.parse("Dev \\#1; dev#1 # contractor").unwrap();
+        assert_eq!(author.name, "Dev #1");
+        assert_eq!(author.email, "dev#1@rocinante.com");
+
+        assert!(author_parser.parse("# James Holden; jholden").is_err());
+    }
+
+    #[test]
+    fn from_identity() {
+        let author = Author::from_identity("CI Bot <ci@rocinante.com>").unwrap();
+        assert_eq!(author.name, "CI Bot");
+        assert_eq!(author.email, "ci@rocinante.com");
+        assert_eq!(Author::from_identity(&author.to_string()).unwrap(), author);
+
+        assert!(Author::from_identity("CI Bot").is_err());
+        assert!(Author::from_identity("<ci@rocinante.com>").is_err());
+        assert!(Author::from_identity("CI Bot <>").is_err());
+        assert!(Author::from_identity("CI Bot <ci@rocinante.com").is_err());
+    }
+
+    #[test]
+    fn parse_coauthors() {
+        let message = "Fix the Epstein drive\n\
+                       \n\
+                       Co-authored-by: is how we credit people, as below.\n\
+                       \n\
+                       Co-authored-by: Alex Kamal <akamal@rocinante.com>\n\
+                       co-authored-by:Amos   Burton <aburton@rocinante.com>  \n\
+                       Co-authored-by: Bobbie Draper bdraper@mars.mil\n\
+                       \tCo-Authored-By : Naomi Nagata <nnagata@rocinante.com>\n\
+                       Signed-off-by: James Holden <jholden@rocinante.com>";
+
+        let coauthors = super::parse_coauthors(message);
+        let names: Vec<_> = coauthors.iter().map(|author| author.name.as_str()).collect();
+        assert_eq!(names, vec!["Alex Kamal", "Amos   Burton", "Naomi Nagata"]);
+        assert_eq!(coauthors[1].email, "aburton@rocinante.com");
+
+        let signers = parse_trailers(message, "signed-off-by");
+        assert_eq!(signers.len(), 1);
+        assert_eq!(signers[0].email, "jholden@rocinante.com");
+        assert!(super::parse_coauthors("Fix the drive").is_empty());
+    }
+
+    #[test]
+    fn gravatar_url() {
+        let author = Author {
+            name: "Gravatar User".into(),
+            email: " MyEmailAddress@example.com ".into(),
+            ..Default::default()
+        };
+        assert_eq!(author.gravatar_url(None, None),
+                   "https://www.gravatar.com/avatar/0bc83cb571cd1c50ba6f3e8a78ef1346");
+        assert_eq!(author.gravatar_url(Some("identicon"), Some(80)),
+                   "https://www.gravatar.com/avatar/0bc83cb571cd1c50ba6f3e8a78ef1346\
+                    ?s=80&d=identicon");
+        assert_eq!(author.gravatar_url(Some("https://example.com/a b.png"), None),
+                   "https://www.gravatar.com/avatar/0bc83cb571cd1c50ba6f3e8a78ef1346\
+                    ?d=https%3A%2F%2Fexample.com%2Fa%20b.png");
+    }
+
+    #[test]
+    fn config_string_round_trip() {
+        let authors = [("James Holden", "jholden@rocinante.com"),
+                       ("C# Dev", "cdev@example.com"),
+                       ("Dev #1", "dev#1@example.com"),
+                       ("Chrisjen Avasarala", "avasarala@un.gov"),
+                       ("Bobbie Draper", "bobbie.draper+mcr@mars.mil")];
+
+        for &(name, email) in &authors {
+            let author = Author {
+                name: name.into(),
+                email: email.into(),
+                ..Default::default()
+            };
+            let raw = author.to_config_string();
+            assert_eq!(raw.parse::<Author>().unwrap(), author);
+
+            let author_parser = AuthorParser {
+                domain: Some("rocinante.com".into()),
+                ..Default::default()
+            };
+            assert_eq!(author_parser.parse(&raw).unwrap(), author);
+        }
+
+        assert_eq!(Author {
+                           name: "James Holden".into(),
+                           email: "jholden@rocinante.com".into(),
+                           ..Default::default()
+                       }
+                       .to_config_string(),
+                   "James Holden; jholden@rocinante.com");
+        assert!("James Holden; jholden".parse::<Author>().is_err());
+    }
 }
@@ -1,9 +1,1028 @@
-use std::collections::HashMap;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::iter::Peekable;
+use std::path::{Path, PathBuf};
+use std::str::Chars;
+use std::thread;
+use std::time::Duration;
+
 use errors::*;
 
 pub trait Config {
     fn get(&self, name: &str) -> Result<String>;
+    /// Whether `name` is set. Backends that can tell a missing name from a
+    /// failed read should, instead of the default of treating any `get`
+    /// error as missing.
+    fn exists(&self, name: &str) -> Result<bool> {
+        Ok(self.get(name).is_ok())
+    }
     fn get_all(&self, glob: &str) -> Result<HashMap<String, String>>;
     fn add(&mut self, name: &str, value: &str) -> Result<()>;
     fn set(&mut self, name: &str, value: &str) -> Result<()>;
+    fn unset(&mut self, name: &str) -> Result<()>;
+    /// What kind of storage this is, like `git` or `json`, for diagnostics.
+    /// Wrappers like `DryRun` report what they wrap.
+    fn backend_name(&self) -> &'static str {
+        "unknown"
+    }
+}
+
+/// Reads config from environment variables, for places where git config
+/// can't be written. A name maps to a variable by uppercasing it and turning
+/// dots into double underscores and dashes into single ones, so
+/// `git-together.authors.jh.tz` is read from `GIT_TOGETHER__AUTHORS__JH__TZ`
+/// and can be told apart from an author `jh-tz`. Writes only last as long as the process,
+/// so they aren't stamped with a `schema-version`.
+pub struct EnvConfig;
+
+impl EnvConfig {
+    pub fn var_name(name: &str) -> String {
+        name.chars()
+            .map(|c| match c {
+                     '.' => "__".into(),
+                     '-' => "_".into(),
+                     c => c.to_ascii_uppercase().to_string(),
+                 })
+            .collect()
+    }
+
+    // The name whose variable is `var_name`, undoing `var_name`.
+    fn name(var_name: &str) -> String {
+        var_name
+            .to_lowercase()
+            .split("__")
+            .map(|part| part.replace('_', "-"))
+            .collect::<Vec<_>>()
+            .join(".")
+    }
+}
+
+impl Config for EnvConfig {
+    fn get(&self, name: &str) -> Result<String> {
+        let var_name = EnvConfig::var_name(name);
+        env::var(&var_name).chain_err(|| format!("error getting env var '{}'", var_name))
+    }
+
+    fn exists(&self, name: &str) -> Result<bool> {
+        Ok(env::var_os(EnvConfig::var_name(name)).is_some())
+    }
+
+    fn get_all(&self, glob: &str) -> Result<HashMap<String, String>> {
+        let prefix = EnvConfig::var_name(glob);
+        Ok(env::vars()
+               .filter(|&(ref var_name, _)| var_name.starts_with(&prefix))
+               .map(|(var_name, value)| {
+                        let rest = EnvConfig::name(&var_name[prefix.len()..]);
+                        (format!("{}{}", glob, rest), value)
+                    })
+               .collect())
+    }
+
+    fn add(&mut self, name: &str, value: &str) -> Result<()> {
+        self.set(name, value)
+    }
+
+    fn set(&mut self, name: &str, value: &str) -> Result<()> {
+        env::set_var(EnvConfig::var_name(name), value);
+        Ok(())
+    }
+
+    fn unset(&mut self, name: &str) -> Result<()> {
+        env::remove_var(EnvConfig::var_name(name));
+        Ok(())
+    }
+
+    fn backend_name(&self) -> &'static str {
+        "env"
+    }
+}
+
+/// A write that a `DryRun` config held back.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Change {
+    Set(String, String),
+    Unset(String),
+}
+
+/// Wraps a config so that writes are recorded as `Change`s instead of
+/// reaching it. Reads see the recorded writes, so a dry run behaves like the
+/// real one.
+pub struct DryRun<C> {
+    pub inner: C,
+    changes: Vec<Change>,
+}
+
+impl<C: Config> DryRun<C> {
+    pub fn new(inner: C) -> DryRun<C> {
+        DryRun {
+            inner: inner,
+            changes: Vec::new(),
+        }
+    }
+
+    /// The writes that would have been made, in order.
+    pub fn changes(&self) -> &[Change] {
+        &self.changes
+    }
+
+    fn pending(&self, name: &str) -> Option<Option<&str>> {
+        self.changes
+            .iter()
+            .rev()
+            .filter_map(|change| match *change {
+                            Change::Set(ref n, ref value) if n == name => Some(Some(value.as_str())),
+                            Change::Unset(ref n) if n == name => Some(None),
+                            _ => None,
+                        })
+            .next()
+    }
+}
+
+impl<C: Config> Config for DryRun<C> {
+    fn get(&self, name: &str) -> Result<String> {
+        match self.pending(name) {
+            Some(Some(value)) => Ok(value.into()),
+            Some(None) => Err(format!("'{}' is unset in this dry run", name).into()),
+            None => self.inner.get(name),
+        }
+    }
+
+    fn exists(&self, name: &str) -> Result<bool> {
+        match self.pending(name) {
+            Some(value) => Ok(value.is_some()),
+            None => self.inner.exists(name),
+        }
+    }
+
+    fn get_all(&self, glob: &str) -> Result<HashMap<String, String>> {
+        let mut all = self.inner.get_all(glob)?;
+        for change in &self.changes {
+            match *change {
+                Change::Set(ref name, ref value) if name.starts_with(glob) => {
+                    all.insert(name.clone(), value.clone());
+                }
+                Change::Unset(ref name) => {
+                    all.remove(name);
+                }
+                _ => {}
+            }
+        }
+        Ok(all)
+    }
+
+    fn add(&mut self, name: &str, value: &str) -> Result<()> {
+        self.set(name, value)
+    }
+
+    fn set(&mut self, name: &str, value: &str) -> Result<()> {
+        self.changes.push(Change::Set(name.into(), value.into()));
+        Ok(())
+    }
+
+    fn unset(&mut self, name: &str) -> Result<()> {
+        self.get(name)?;
+        self.changes.push(Change::Unset(name.into()));
+        Ok(())
+    }
+
+    fn backend_name(&self) -> &'static str {
+        self.inner.backend_name()
+    }
+}
+
+/// Remembers reads of settings that rarely change, like the domain, so that
+/// resolving a whole mob doesn't read them again for every author. Only the
+/// given names are cached, and only once they've been read successfully.
+/// Writes through the wrapper drop the names they touch; `invalidate` drops
+/// everything.
+pub struct CachedConfig<C> {
+    pub inner: C,
+    cacheable: Vec<String>,
+    cache: RefCell<HashMap<String, String>>,
+}
+
+impl<C: Config> CachedConfig<C> {
+    pub fn new(inner: C, cacheable: &[&str]) -> CachedConfig<C> {
+        CachedConfig {
+            inner: inner,
+            cacheable: cacheable.iter().map(|&name| name.into()).collect(),
+            cache: RefCell::new(HashMap::new()),
+        }
+    }
+
+    pub fn invalidate(&self) {
+        self.cache.borrow_mut().clear();
+    }
+}
+
+impl<C: Config> Config for CachedConfig<C> {
+    fn get(&self, name: &str) -> Result<String> {
+        if let Some(value) = self.cache.borrow().get(name) {
+            return Ok(value.clone());
+        }
+
+        let value = self.inner.get(name)?;
+        if self.cacheable.iter().any(|cacheable| cacheable == name) {
+            self.cache
+                .borrow_mut()
+                .insert(name.into(), value.clone());
+        }
+        Ok(value)
+    }
+
+    fn exists(&self, name: &str) -> Result<bool> {
+        if self.cache.borrow().contains_key(name) {
+            return Ok(true);
+        }
+        self.inner.exists(name)
+    }
+
+    fn get_all(&self, glob: &str) -> Result<HashMap<String, String>> {
+        self.inner.get_all(glob)
+    }
+
+    fn add(&mut self, name: &str, value: &str) -> Result<()> {
+        self.cache.borrow_mut().remove(name);
+        self.inner.add(name, value)
+    }
+
+    fn set(&mut self, name: &str, value: &str) -> Result<()> {
+        self.cache.borrow_mut().remove(name);
+        self.inner.set(name, value)
+    }
+
+    fn unset(&mut self, name: &str) -> Result<()> {
+        self.cache.borrow_mut().remove(name);
+        self.inner.unset(name)
+    }
+
+    fn backend_name(&self) -> &'static str {
+        self.inner.backend_name()
+    }
+}
+
+/// Falls back to `defaults` for names the inner config doesn't have. A
+/// failed read is still an error rather than a default, and writes always
+/// go to the inner config, so a default is never written out by reading it.
+pub struct DefaultingConfig<C> {
+    pub inner: C,
+    defaults: HashMap<String, String>,
+}
+
+impl<C: Config> DefaultingConfig<C> {
+    pub fn new(inner: C, defaults: HashMap<String, String>) -> DefaultingConfig<C> {
+        DefaultingConfig {
+            inner: inner,
+            defaults: defaults,
+        }
+    }
+}
+
+impl<C: Config> Config for DefaultingConfig<C> {
+    fn get(&self, name: &str) -> Result<String> {
+        match self.defaults.get(name) {
+            Some(default) if !self.inner.exists(name)? => Ok(default.clone()),
+            _ => self.inner.get(name),
+        }
+    }
+
+    fn exists(&self, name: &str) -> Result<bool> {
+        Ok(self.defaults.contains_key(name) || self.inner.exists(name)?)
+    }
+
+    fn get_all(&self, glob: &str) -> Result<HashMap<String, String>> {
+        let mut all = self.inner.get_all(glob)?;
+        for (name, default) in &self.defaults {
+            if name.starts_with(glob) && !all.contains_key(name) {
+                all.insert(name.clone(), default.clone());
+            }
+        }
+        Ok(all)
+    }
+
+    fn add(&mut self, name: &str, value: &str) -> Result<()> {
+        self.inner.add(name, value)
+    }
+
+    fn set(&mut self, name: &str, value: &str) -> Result<()> {
+        self.inner.set(name, value)
+    }
+
+    fn unset(&mut self, name: &str) -> Result<()> {
+        self.inner.unset(name)
+    }
+
+    fn backend_name(&self) -> &'static str {
+        self.inner.backend_name()
+    }
+}
+
+/// Reads and writes git-together settings as a JSON object, like
+/// `{"domain": "rocinante.com", "authors": {"jh": "James Holden; jholden"}}`.
+///
+/// Names in the `git-together.` namespace drop it, and their first segment
+/// is a top-level key: `git-together.authors.jh.tz` is `"jh.tz"` in the
+/// `"authors"` object. Any other name, like `user.name`, is a top-level key
+/// as-is. Writes replace the whole file, pretty-printed. The first
+/// git-together setting written also stamps `schema-version`, as with git.
+///
+/// Several processes can share the file. A write takes a `.json.lock` file
+/// next to it, rereads the file so that others' changes aren't lost, and
+/// renames the new contents into place, so a reader only ever sees a whole
+/// file from before or after the write.
+pub struct JsonConfig {
+    path: PathBuf,
+    values: BTreeMap<String, String>,
+}
+
+impl JsonConfig {
+    /// Loads `path`, which doesn't need to exist until the first write.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<JsonConfig> {
+        let path = path.as_ref().to_path_buf();
+        let values = JsonConfig::load(&path)?;
+        Ok(JsonConfig {
+               path: path,
+               values: values,
+           })
+    }
+
+    fn load(path: &Path) -> Result<BTreeMap<String, String>> {
+        let mut raw = String::new();
+        match File::open(&path) {
+            Ok(mut file) => {
+                file.read_to_string(&mut raw)
+                    .chain_err(|| format!("error reading '{}'", path.display()))?;
+            }
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => {
+                return Err(err).chain_err(|| format!("error opening '{}'", path.display()));
+            }
+        }
+
+        let mut values = BTreeMap::new();
+        if !raw.trim().is_empty() {
+            let mut chars = raw.chars().peekable();
+            let object = json::parse(&mut chars)
+                .chain_err(|| format!("invalid JSON in '{}'", path.display()))?;
+            JsonConfig::flatten(object, &mut values)?;
+        }
+        Ok(values)
+    }
+
+    // Rereads the file and applies `change` to it with the lock held.
+    fn update<F>(&mut self, change: F) -> Result<()>
+        where F: FnOnce(&mut BTreeMap<String, String>) -> Result<()>
+    {
+        let _lock = FileLock::acquire(self.path.with_extension("json.lock"))?;
+        let mut values = JsonConfig::load(&self.path)?;
+        change(&mut values)?;
+        self.values = values;
+        self.save()
+    }
+
+    fn flatten(object: BTreeMap<String, json::Value>,
+               values: &mut BTreeMap<String, String>)
+               -> Result<()> {
+        for (key, value) in object {
+            match value {
+                json::Value::String(value) if key.contains('.') => {
+                    values.insert(key, value);
+                }
+                json::Value::String(value) => {
+                    values.insert(format!("git-together.{}", key), value);
+                }
+                json::Value::Object(section) => {
+                    for (name, value) in section {
+                        match value {
+                            json::Value::String(value) => {
+                                values.insert(format!("git-together.{}.{}", key, name), value);
+                            }
+                            json::Value::Object(_) => {
+                                return Err(format!("'{}.{}' is nested too deeply", key, name)
+                                               .into())
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn save(&self) -> Result<()> {
+        let mut object = BTreeMap::new();
+        for (name, value) in &self.values {
+            let value = json::Value::String(value.clone());
+            let name = match name.find("git-together.") {
+                Some(0) => &name["git-together.".len()..],
+                _ => {
+                    object.insert(name.clone(), value);
+                    continue;
+                }
+            };
+            match name.find('.') {
+                Some(i) => {
+                    let section = object
+                        .entry(name[..i].into())
+                        .or_insert_with(|| json::Value::Object(BTreeMap::new()));
+                    if let json::Value::Object(ref mut section) = *section {
+                        section.insert(name[i + 1..].into(), value);
+                    }
+                }
+                None => {
+                    object.insert(name.into(), value);
+                }
+            }
+        }
+
+        let mut raw = String::new();
+        json::write(&object, 0, &mut raw);
+        raw.push('\n');
+
+        // Write alongside and rename over, so a failed write can't leave a
+        // truncated file behind.
+        let tmp = self.path
+            .with_extension(format!("json.tmp.{}", ::std::process::id()));
+        File::create(&tmp)
+            .and_then(|mut file| file.write_all(raw.as_bytes()))
+            .and_then(|_| fs::rename(&tmp, &self.path))
+            .chain_err(|| format!("error writing '{}'", self.path.display()))
+    }
+}
+
+impl Config for JsonConfig {
+    fn get(&self, name: &str) -> Result<String> {
+        self.values
+            .get(name)
+            .cloned()
+            .ok_or_else(|| format!("error getting '{}'", name).into())
+    }
+
+    fn exists(&self, name: &str) -> Result<bool> {
+        Ok(self.values.contains_key(name))
+    }
+
+    fn get_all(&self, glob: &str) -> Result<HashMap<String, String>> {
+        Ok(self.values
+               .iter()
+               .filter(|&(name, _)| name.starts_with(glob))
+               .map(|(name, value)| (name.clone(), value.clone()))
+               .collect())
+    }
+
+    fn add(&mut self, name: &str, value: &str) -> Result<()> {
+        self.set(name, value)
+    }
+
+    fn set(&mut self, name: &str, value: &str) -> Result<()> {
+        let key = format!("{}.schema-version", ::NAMESPACE);
+        let stamps = name.starts_with(&format!("{}.", ::NAMESPACE)) && name != key;
+        self.update(|values| {
+                        values.insert(name.into(), value.into());
+                        if stamps && !values.contains_key(&key) {
+                            values.insert(key, ::SCHEMA_VERSION.to_string());
+                        }
+                        Ok(())
+                    })
+    }
+
+    fn unset(&mut self, name: &str) -> Result<()> {
+        self.update(|values| {
+                        values
+                            .remove(name)
+                            .map(|_| ())
+                            .ok_or_else(|| format!("error unsetting '{}'", name).into())
+                    })
+    }
+
+    fn backend_name(&self) -> &'static str {
+        "json"
+    }
+}
+
+// Held while the file is rewritten. Creating the lock file fails while
+// someone else holds it, so this retries for a few seconds before giving up.
+struct FileLock {
+    path: PathBuf,
+}
+
+impl FileLock {
+    fn acquire(path: PathBuf) -> Result<FileLock> {
+        for _ in 0..500 {
+            match fs::OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(_) => return Ok(FileLock { path: path }),
+                Err(ref err) if err.kind() == io::ErrorKind::AlreadyExists => {
+                    thread::sleep(Duration::from_millis(10));
+                }
+                Err(err) => {
+                    return Err(err).chain_err(|| format!("error locking '{}'", path.display()));
+                }
+            }
+        }
+        Err(format!("timed out waiting for '{}' (remove it if nothing is writing)",
+                    path.display())
+                    .into())
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+// Just enough JSON for a config file: objects of strings, nested objects,
+// and scalars, which are kept as their literal text.
+mod json {
+    use super::*;
+
+    pub enum Value {
+        String(String),
+        Object(BTreeMap<String, Value>),
+    }
+
+    pub fn parse(chars: &mut Peekable<Chars>) -> Result<BTreeMap<String, Value>> {
+        let object = match value(chars)? {
+            Value::Object(object) => object,
+            Value::String(_) => {
+                return Err("expected an object".into());
+            }
+        };
+        skip_whitespace(chars);
+        match chars.next() {
+            None => Ok(object),
+            Some(c) => Err(format!("unexpected '{}' after the object", c).into()),
+        }
+    }
+
+    fn value(chars: &mut Peekable<Chars>) -> Result<Value> {
+        skip_whitespace(chars);
+        match chars.peek().cloned() {
+            Some('{') => {
+                chars.next();
+                object(chars).map(Value::Object)
+            }
+            Some('"') => {
+                chars.next();
+                string(chars).map(Value::String)
+            }
+            Some(c) if c == '-' || c.is_ascii_alphanumeric() => {
+                let mut scalar = String::new();
+                while let Some(&c) = chars.peek() {
+                    if !(c.is_ascii_alphanumeric() || "+-.".contains(c)) {
+                        break;
+                    }
+                    scalar.push(c);
+                    chars.next();
+                }
+                Ok(Value::String(scalar))
+            }
+            Some(c) => Err(format!("unexpected '{}'", c).into()),
+            None => Err("unexpected end of input".into()),
+        }
+    }
+
+    fn object(chars: &mut Peekable<Chars>) -> Result<BTreeMap<String, Value>> {
+        let mut object = BTreeMap::new();
+        skip_whitespace(chars);
+        if chars.peek() == Some(&'}') {
+            chars.next();
+            return Ok(object);
+        }
+
+        loop {
+            skip_whitespace(chars);
+            if chars.next() != Some('"') {
+                return Err("expected a key".into());
+            }
+            let key = string(chars)?;
+            skip_whitespace(chars);
+            if chars.next() != Some(':') {
+                return Err(format!("expected ':' after '{}'", key).into());
+            }
+            let value = value(chars)?;
+            object.insert(key, value);
+
+            skip_whitespace(chars);
+            match chars.next() {
+                Some(',') => {}
+                Some('}') => {
+                    return Ok(object);
+                }
+                _ => {
+                    return Err("expected ',' or '}'".into());
+                }
+            }
+        }
+    }
+
+    fn string(chars: &mut Peekable<Chars>) -> Result<String> {
+        let mut string = String::new();
+        loop {
+            match chars.next() {
+                Some('"') => {
+                    return Ok(string);
+                }
+                Some('\\') => {
+                    let c = match chars.next() {
+                        Some('n') => '\n',
+                        Some('t') => '\t',
+                        Some('r') => '\r',
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some('u') => unicode(chars)?,
+                        Some(c @ '"') | Some(c @ '\\') | Some(c @ '/') => c,
+                        _ => {
+                            return Err("invalid escape".into());
+                        }
+                    };
+                    string.push(c);
+                }
+                Some(c) => string.push(c),
+                None => {
+                    return Err("unterminated string".into());
+                }
+            }
+        }
+    }
+
+    fn unicode(chars: &mut Peekable<Chars>) -> Result<char> {
+        fn hex(chars: &mut Peekable<Chars>) -> Result<u32> {
+            let digits: String = chars.take(4).collect();
+            u32::from_str_radix(&digits, 16).chain_err(|| format!("invalid \\u{}", digits))
+        }
+
+        let high = hex(chars)?;
+        let code = if high >= 0xd800 && high < 0xdc00 {
+            if chars.next() != Some('\\') || chars.next() != Some('u') {
+                return Err("unpaired surrogate".into());
+            }
+            let low = hex(chars)?;
+            0x10000 + ((high - 0xd800) << 10) + (low.wrapping_sub(0xdc00) & 0x3ff)
+        } else {
+            high
+        };
+        ::std::char::from_u32(code).ok_or_else(|| "invalid \\u escape".into())
+    }
+
+    fn skip_whitespace(chars: &mut Peekable<Chars>) {
+        while chars.peek().map_or(false, |c| c.is_whitespace()) {
+            chars.next();
+        }
+    }
+
+    pub fn write(object: &BTreeMap<String, Value>, indent: usize, out: &mut String) {
+        if object.is_empty() {
+            out.push_str("{}");
+            return;
+        }
+
+        out.push_str("{\n");
+        for (i, (key, value)) in object.iter().enumerate() {
+            out.push_str(&"  ".repeat(indent + 1));
+            write_string(key, out);
+            out.push_str(": ");
+            match *value {
+                Value::String(ref value) => write_string(value, out),
+                Value::Object(ref object) => write(object, indent + 1, out),
+            }
+            out.push_str(if i + 1 < object.len() { ",\n" } else { "\n" });
+        }
+        out.push_str(&"  ".repeat(indent));
+        out.push('}');
+    }
+
+    fn write_string(string: &str, out: &mut String) {
+        out.push('"');
+        for c in string.chars() {
+            match c {
+                '"' => out.push_str("\\\""),
+                '\\' => out.push_str("\\\\"),
+                '\n' => out.push_str("\\n"),
+                '\t' => out.push_str("\\t"),
+                '\r' => out.push_str("\\r"),
+                c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+                c => out.push(c),
+            }
+        }
+        out.push('"');
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn env_var_name() {
+        assert_eq!(EnvConfig::var_name("git-together.authors.jh"),
+                   "GIT_TOGETHER__AUTHORS__JH");
+        assert_eq!(EnvConfig::var_name("git-together.domain"),
+                   "GIT_TOGETHER__DOMAIN");
+        assert_eq!(EnvConfig::var_name("git-together.active-set-at"),
+                   "GIT_TOGETHER__ACTIVE_SET_AT");
+
+        for name in &["git-together.authors.jh.tz",
+                      "git-together.authors.jh-tz",
+                      "git-together.active-set-at"] {
+            assert_eq!(EnvConfig::name(&EnvConfig::var_name(name)), *name);
+        }
+    }
+
+    #[test]
+    fn backend_name() {
+        assert_eq!(EnvConfig.backend_name(), "env");
+        assert_eq!(DryRun::new(CachedConfig::new(EnvConfig, &[])).backend_name(),
+                   "env");
+
+        let path = env::temp_dir().join(format!("git-together-backend-{}.json",
+                                                ::std::process::id()));
+        assert_eq!(JsonConfig::open(&path).unwrap().backend_name(), "json");
+    }
+
+    #[test]
+    fn dry_run_get_all() {
+        let path = env::temp_dir().join(format!("git-together-dry-run-{}.json",
+                                                ::std::process::id()));
+        let _ = fs::remove_file(&path);
+        let mut config = DryRun::new(JsonConfig::open(&path).unwrap());
+
+        config
+            .set("git-together.authors.jh", "James Holden; jholden")
+            .unwrap();
+        assert_eq!(config.get_all("git-together.authors.").unwrap().len(), 1);
+        assert!(config.get_all("authors.").unwrap().is_empty());
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn defaulting_config() {
+        let path = env::temp_dir().join(format!("git-together-defaulting-{}.json",
+                                                ::std::process::id()));
+        let _ = fs::remove_file(&path);
+        let mut defaults = HashMap::new();
+        defaults.insert("git-together.trailer-order".to_string(), "active".to_string());
+        let mut config = DefaultingConfig::new(JsonConfig::open(&path).unwrap(), defaults);
+
+        assert_eq!(config.get("git-together.trailer-order").unwrap(), "active");
+        assert!(config.exists("git-together.trailer-order").unwrap());
+        assert!(config.get("git-together.domain").is_err());
+        assert_eq!(config.get_all("git-together.").unwrap().len(), 1);
+        assert!(config.get_all("trailer-order").unwrap().is_empty());
+
+        config
+            .set("git-together.trailer-order", "alpha-name")
+            .unwrap();
+        assert_eq!(config.get("git-together.trailer-order").unwrap(), "alpha-name");
+        assert_eq!(config.inner.get("git-together.trailer-order").unwrap(),
+                   "alpha-name");
+        assert_eq!(config.get_all("git-together.").unwrap()["git-together.trailer-order"],
+                   "alpha-name");
+
+        config.unset("git-together.trailer-order").unwrap();
+        assert_eq!(config.get("git-together.trailer-order").unwrap(), "active");
+        assert!(!config.inner.exists("git-together.trailer-order").unwrap());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn env_config() {
+        let mut config = EnvConfig;
+
+        assert!(config.get("git-together-env-test.authors.jh").is_err());
+
+        config
+            .set("git-together-env-test.authors.jh", "James Holden; jholden")
+            .unwrap();
+        config
+            .set("git-together-env-test.authors.nn", "Naomi Nagata; nnagata")
+            .unwrap();
+        config
+            .set("git-together-env-test.authors.jh.tz", "America/New_York")
+            .unwrap();
+        assert_eq!(env::var("GIT_TOGETHER_ENV_TEST__AUTHORS__JH").unwrap(),
+                   "James Holden; jholden");
+        assert_eq!(config.get("git-together-env-test.authors.jh").unwrap(),
+                   "James Holden; jholden");
+
+        assert_eq!(env::var("GIT_TOGETHER_ENV_TEST__AUTHORS__JH__TZ").unwrap(),
+                   "America/New_York");
+
+        let all = config.get_all("git-together-env-test.authors.").unwrap();
+        assert_eq!(all.len(), 3);
+        assert_eq!(all["git-together-env-test.authors.nn"],
+                   "Naomi Nagata; nnagata");
+        assert_eq!(all["git-together-env-test.authors.jh.tz"], "America/New_York");
+        assert_eq!(config.get_all("git-together-env-test.").unwrap().len(), 3);
+
+        assert!(config.exists("git-together-env-test.authors.nn").unwrap());
+        config.unset("git-together-env-test.authors.nn").unwrap();
+        assert!(config.get("git-together-env-test.authors.nn").is_err());
+        assert!(!config.exists("git-together-env-test.authors.nn").unwrap());
+    }
+
+    #[test]
+    fn cached_config() {
+        use std::cell::Cell;
+
+        struct CountingConfig {
+            data: HashMap<String, String>,
+            gets: Cell<usize>,
+        }
+
+        impl Config for CountingConfig {
+            fn get(&self, name: &str) -> Result<String> {
+                self.gets.set(self.gets.get() + 1);
+                self.data
+                    .get(name)
+                    .cloned()
+                    .ok_or_else(|| format!("name not found: '{}'", name).into())
+            }
+
+            fn get_all(&self, _: &str) -> Result<HashMap<String, String>> {
+                Ok(self.data.clone())
+            }
+
+            fn add(&mut self, name: &str, value: &str) -> Result<()> {
+                self.set(name, value)
+            }
+
+            fn set(&mut self, name: &str, value: &str) -> Result<()> {
+                self.data.insert(name.into(), value.into());
+                Ok(())
+            }
+
+            fn unset(&mut self, name: &str) -> Result<()> {
+                self.data.remove(name);
+                Ok(())
+            }
+        }
+
+        let inner = CountingConfig {
+            data: vec![("git-together.domain".to_string(), "rocinante.com".to_string()),
+                       ("git-together.authors.jh".into(), "James Holden; jholden".into())]
+                    .into_iter()
+                    .collect(),
+            gets: Cell::new(0),
+        };
+        let mut config = CachedConfig::new(inner, &["git-together.domain"]);
+
+        for _ in 0..3 {
+            assert_eq!(config.get("git-together.domain").unwrap(), "rocinante.com");
+        }
+        assert_eq!(config.inner.gets.get(), 1);
+
+        for _ in 0..3 {
+            config.get("git-together.authors.jh").unwrap();
+        }
+        assert_eq!(config.inner.gets.get(), 4);
+
+        config.set("git-together.domain", "tycho.com").unwrap();
+        assert_eq!(config.get("git-together.domain").unwrap(), "tycho.com");
+        assert_eq!(config.get("git-together.domain").unwrap(), "tycho.com");
+        assert_eq!(config.inner.gets.get(), 5);
+
+        config.inner.data.insert("git-together.domain".into(), "ceres.com".into());
+        assert_eq!(config.get("git-together.domain").unwrap(), "tycho.com");
+        config.invalidate();
+        assert_eq!(config.get("git-together.domain").unwrap(), "ceres.com");
+
+        config.unset("git-together.domain").unwrap();
+        assert!(config.get("git-together.domain").is_err());
+    }
+
+    #[test]
+    fn json_config() {
+        let path = env::temp_dir().join(format!("git-together-json-{}.json",
+                                                ::std::process::id()));
+        File::create(&path)
+            .unwrap()
+            .write_all(br#"{
+                "domain": "rocinante.com",
+                "active": "jh+nn",
+                "max-active": 3,
+                "authors": {
+                    "jh": "James Holden; jholden",
+                    "nn": "Naomi Nagata; nnagata",
+                    "jh.tz": "America/New_York"
+                },
+                "user.name": "Ship \"Roci\" \u00c9"
+            }"#)
+            .unwrap();
+
+        let mut config = JsonConfig::open(&path).unwrap();
+        assert_eq!(config.get("git-together.domain").unwrap(), "rocinante.com");
+        assert_eq!(config.get("git-together.max-active").unwrap(), "3");
+        assert_eq!(config.get("git-together.authors.jh").unwrap(),
+                   "James Holden; jholden");
+        assert_eq!(config.get("git-together.authors.jh.tz").unwrap(),
+                   "America/New_York");
+        assert_eq!(config.get("user.name").unwrap(), "Ship \"Roci\" \u{c9}");
+        assert!(config.get("git-together.authors.ak").is_err());
+        assert_eq!(config.get_all("git-together.authors.").unwrap().len(), 3);
+
+        config
+            .set("git-together.authors.ak", "Alex Kamal; akamal")
+            .unwrap();
+        assert!(config.exists("git-together.active").unwrap());
+        config.unset("git-together.active").unwrap();
+        assert!(config.unset("git-together.active").is_err());
+        assert!(!config.exists("git-together.active").unwrap());
+        assert!(config.exists("git-together.authors.jh.tz").unwrap());
+
+        let mut raw = String::new();
+        File::open(&path)
+            .unwrap()
+            .read_to_string(&mut raw)
+            .unwrap();
+        assert_eq!(raw,
+                   r#"{
+  "authors": {
+    "ak": "Alex Kamal; akamal",
+    "jh": "James Holden; jholden",
+    "jh.tz": "America/New_York",
+    "nn": "Naomi Nagata; nnagata"
+  },
+  "domain": "rocinante.com",
+  "max-active": "3",
+  "schema-version": "1",
+  "user.name": "Ship \"Roci\" É"
+}
+"#);
+
+        let reopened = JsonConfig::open(&path).unwrap();
+        assert_eq!(reopened.get_all("git-together.").unwrap(),
+                   config.get_all("git-together.").unwrap());
+        assert_eq!(reopened.get("user.name").unwrap(), "Ship \"Roci\" \u{c9}");
+
+        fs::remove_file(&path).unwrap();
+        assert!(JsonConfig::open(&path)
+                    .unwrap()
+                    .get("git-together.domain")
+                    .is_err());
+
+        File::create(&path)
+            .unwrap()
+            .write_all(b"{\"authors\": [\"jh\"]}")
+            .unwrap();
+        assert!(JsonConfig::open(&path).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn json_config_concurrent() {
+        let path = env::temp_dir().join(format!("git-together-json-concurrent-{}.json",
+                                                ::std::process::id()));
+        let _ = fs::remove_file(&path);
+        let mut config = JsonConfig::open(&path).unwrap();
+        for i in 0..200 {
+            config
+                .set(&format!("git-together.authors.a{}", i), "Amos Burton; aburton")
+                .unwrap();
+        }
+        config.set("git-together.active", "jh+nn").unwrap();
+
+        let writer = {
+            let path = path.clone();
+            thread::spawn(move || {
+                let mut config = JsonConfig::open(&path).unwrap();
+                for i in 0..50 {
+                    let active = if i % 2 == 0 { "ab+bd+ca" } else { "jh+nn" };
+                    config.set("git-together.active", active).unwrap();
+                }
+            })
+        };
+        let other_writer = {
+            let path = path.clone();
+            thread::spawn(move || {
+                let mut config = JsonConfig::open(&path).unwrap();
+                for i in 0..50 {
+                    config
+                        .set("git-together.active-set-at", &i.to_string())
+                        .unwrap();
+                }
+            })
+        };
+
+        for _ in 0..100 {
+            let active = JsonConfig::open(&path)
+                .unwrap()
+                .get("git-together.active")
+                .unwrap();
+            assert!(active == "jh+nn" || active == "ab+bd+ca", "{}", active);
+        }
+        writer.join().unwrap();
+        other_writer.join().unwrap();
+
+        let config = JsonConfig::open(&path).unwrap();
+        assert_eq!(config.get("git-together.active").unwrap(), "jh+nn");
+        assert_eq!(config.get("git-together.active-set-at").unwrap(), "49");
+        assert_eq!(config.get_all("git-together.authors.").unwrap().len(), 200);
+        assert!(!path.with_extension("json.lock").exists());
+        fs::remove_file(&path).unwrap();
+    }
 }
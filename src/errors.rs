@@ -1 +1,54 @@
-error_chain!{}
+error_chain!{
+    errors {
+        AuthorNotFound(init: String) {
+            description("author not found")
+            display("author not found for '{}'", init)
+        }
+        AliasCycle(init: String) {
+            description("alias cycle")
+            display("alias cycle for '{}'", init)
+        }
+        NoPreviousPair {
+            description("no previous pair")
+            display("no previous pair to restore")
+        }
+        NonUtf8Value(name: String) {
+            description("config value is not valid UTF-8")
+            display("git config value for '{}' is not valid UTF-8 \
+                     (set git-together.lossy-config to decode it anyway)",
+                    name)
+        }
+        TooManyActive(got: usize, max: usize) {
+            description("too many active authors")
+            display("{} active authors is more than max-active ({})", got, max)
+        }
+        DisallowedDomain(domain: String) {
+            description("email domain is not allowed")
+            display("'{}' is not in allowed-domains", domain)
+        }
+        InvalidEmail(email: String, reason: String) {
+            description("invalid email")
+            display("invalid email '{}': {}", email, reason)
+        }
+        InvalidDomain(domain: String) {
+            description("invalid domain")
+            display("'{}' is not a valid domain", domain)
+        }
+        FullEmailNotAllowed(init: String) {
+            description("full email not allowed")
+            display("'{}' has a full email, but require-domain-emails is set", init)
+        }
+        NeedsTwoForCommitter(mode: String) {
+            description("committer mode needs two active authors")
+            display("committer-mode '{}' needs a second active author to commit", mode)
+        }
+        InvalidInit(init: String, reason: String) {
+            description("invalid initials")
+            display("invalid initials '{}': {}", init, reason)
+        }
+        NotInActive(init: String) {
+            description("author is not active")
+            display("'{}' is not one of the active authors", init)
+        }
+    }
+}
@@ -1,5 +1,7 @@
 use std::collections::HashMap;
 use std::env;
+use std::path::{Path, PathBuf};
+use std::str;
 
 use git2;
 
@@ -19,18 +21,82 @@ impl Repo {
         Ok(Repo { repo: repo })
     }
 
+    /// The repo git itself would use, following `GIT_DIR`,
+    /// `GIT_CEILING_DIRECTORIES`, and the like.
+    pub fn from_env() -> Result<Self> {
+        git2::Repository::open_from_env()
+            .map(|repo| Repo { repo: repo })
+            .chain_err(|| "not in a git repository")
+    }
+
+    pub fn open(path: &Path) -> Result<Self> {
+        git2::Repository::open(path)
+            .map(|repo| Repo { repo: repo })
+            .chain_err(|| format!("not a git repository: '{}'", path.display()))
+    }
+
     pub fn config(&self) -> Result<Config> {
         self.repo
             .config()
-            .map(|config| Config { config: config })
+            .map(|config| {
+                     Config {
+                         config: config,
+                         source: Source::Repo(self.repo.path().to_path_buf()),
+                     }
+                 })
             .chain_err(|| "")
     }
 
+    /// The commit `HEAD` points to, if there is one yet.
+    pub fn head_id(&self) -> Option<String> {
+        self.repo
+            .head()
+            .ok()
+            .and_then(|head| head.target())
+            .map(|oid| oid.to_string())
+    }
+
+    /// The first parent of the commit `HEAD` points at, which is the same
+    /// for a commit and its amended replacement. None for a root commit.
+    pub fn head_parent_id(&self) -> Option<String> {
+        self.repo
+            .head()
+            .ok()
+            .and_then(|head| head.target())
+            .and_then(|oid| self.repo.find_commit(oid).ok())
+            .and_then(|commit| commit.parent_id(0).ok())
+            .map(|oid| oid.to_string())
+    }
+
+    /// Whether this is a bare repo, like the ones server-side hooks run in,
+    /// with no worktree.
+    pub fn is_bare(&self) -> bool {
+        self.repo.is_bare()
+    }
+
+    /// The branch checked out in the worktree, if any. Bare repos have
+    /// nothing checked out, even though their `HEAD` names a branch.
+    pub fn current_branch(&self) -> Option<String> {
+        if self.is_bare() {
+            return None;
+        }
+        self.repo
+            .head()
+            .ok()
+            .and_then(|head| if head.is_branch() {
+                          head.shorthand().map(String::from)
+                      } else {
+                          None
+                      })
+    }
+
     pub fn auto_include(&self, filename: &str) -> Result<()> {
         let include_path = format!("../{}", filename);
 
+        // A bare repo has no worktree to find the file in, and its config is
+        // only ever the local one.
         let workdir = match self.repo.workdir() {
-            Some(dir) => dir,
+            Some(dir) if !self.is_bare() => dir,
             _ => {
                 return Ok(());
             }
@@ -79,23 +145,95 @@ impl Repo {
     }
 }
 
+/// Reads git config the way git does, including any files pulled in with
+/// `include.path`, so a shared author list can live in its own file. Writes
+/// always go to the file itself (the repo's `.git/config` for a repo), never
+/// an included one.
 pub struct Config {
     config: git2::Config,
+    source: Source,
+}
+
+// Where a `Config` was opened from, so that it can be opened again.
+enum Source {
+    Default,
+    Repo(PathBuf),
+    File(PathBuf),
 }
 
 impl Config {
     pub fn new() -> Result<Self> {
         git2::Config::open_default()
-            .map(|config| Config { config: config })
+            .map(|config| {
+                     Config {
+                         config: config,
+                         source: Source::Default,
+                     }
+                 })
             .chain_err(|| "")
     }
+
+    /// Only the config in the file at `path`, as with `GIT_CONFIG`.
+    pub fn open(path: &Path) -> Result<Self> {
+        git2::Config::open(path)
+            .map(|config| {
+                     Config {
+                         config: config,
+                         source: Source::File(path.to_path_buf()),
+                     }
+                 })
+            .chain_err(|| format!("error opening git config '{}'", path.display()))
+    }
+
+    // Whether writing `name` should also mark the config with the schema
+    // version, as the first git-together setting written to it. This has to
+    // be asked before the write, since the handle can't be read after it
+    // until it's reloaded.
+    fn needs_stamp(&self, name: &str) -> Result<bool> {
+        let key = format!("{}.schema-version", ::NAMESPACE);
+        if !name.starts_with(&format!("{}.", ::NAMESPACE)) || name == key {
+            return Ok(false);
+        }
+        Ok(!config::Config::exists(self, &key)?)
+    }
+
+    fn stamp(&mut self) -> Result<()> {
+        let key = format!("{}.schema-version", ::NAMESPACE);
+        self.config
+            .set_str(&key, &::SCHEMA_VERSION.to_string())
+            .chain_err(|| format!("error setting git config '{}'", key))
+    }
+
+    // libgit2 drops every entry from an open config that has includes once
+    // it's written to, so writes are followed by opening it again.
+    fn reload(&mut self) -> Result<()> {
+        let config = match self.source {
+            Source::Default => git2::Config::open_default(),
+            Source::Repo(ref path) => git2::Repository::open(path).and_then(|repo| repo.config()),
+            Source::File(ref path) => git2::Config::open(path),
+        };
+        self.config = config.chain_err(|| "error reloading git config")?;
+        Ok(())
+    }
 }
 
 impl config::Config for Config {
     fn get(&self, name: &str) -> Result<String> {
-        self.config
-            .get_string(name)
-            .chain_err(|| format!("error getting git config for '{}'", name))
+        let entry = self.config
+            .get_entry(name)
+            .chain_err(|| format!("error getting git config for '{}'", name))?;
+        let lossy = self.config
+            .get_bool("git-together.lossy-config")
+            .unwrap_or(false);
+        decode(name, entry.value_bytes(), lossy)
+    }
+
+    fn exists(&self, name: &str) -> Result<bool> {
+        match self.config.get_entry(name) {
+            Ok(_) => Ok(true),
+            Err(ref err) if err.code() == git2::ErrorCode::NotFound => Ok(false),
+            Err(err) => Err(err).chain_err(|| format!("error getting git config for '{}'", name)),
+        }
     }
 
     fn get_all(&self, glob: &str) -> Result<HashMap<String, String>> {
@@ -113,14 +251,254 @@ impl config::Config for Config {
     }
 
     fn add(&mut self, name: &str, value: &str) -> Result<()> {
+        let stamp = self.needs_stamp(name)?;
         self.config
             .set_multivar(name, "^$", value)
-            .chain_err(|| format!("error adding git config '{}': '{}'", name, value))
+            .chain_err(|| format!("error adding git config '{}': '{}'", name, value))?;
+        if stamp {
+            self.stamp()?;
+        }
+        self.reload()
     }
 
     fn set(&mut self, name: &str, value: &str) -> Result<()> {
+        let stamp = self.needs_stamp(name)?;
         self.config
             .set_str(name, value)
-            .chain_err(|| format!("error setting git config '{}': '{}'", name, value))
+            .chain_err(|| format!("error setting git config '{}': '{}'", name, value))?;
+        if stamp {
+            self.stamp()?;
+        }
+        self.reload()
+    }
+
+    fn unset(&mut self, name: &str) -> Result<()> {
+        self.config
+            .remove(name)
+            .chain_err(|| format!("error unsetting git config '{}'", name))?;
+        self.reload()
+    }
+
+    fn backend_name(&self) -> &'static str {
+        "git"
+    }
+}
+
+fn decode(name: &str, bytes: &[u8], lossy: bool) -> Result<String> {
+    match str::from_utf8(bytes) {
+        Ok(value) => Ok(value.into()),
+        Err(_) if lossy => Ok(String::from_utf8_lossy(bytes).into_owned()),
+        Err(err) => Err(err).chain_err(|| ErrorKind::NonUtf8Value(name.into())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+    use std::path::PathBuf;
+    use std::process;
+
+    use config::Config;
+
+    fn temp_repo(name: &str) -> (PathBuf, Repo) {
+        let path = env::temp_dir().join(format!("git-together-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&path);
+        let repo = git2::Repository::init(&path).unwrap();
+        (path, Repo { repo: repo })
+    }
+
+    #[test]
+    fn config() {
+        let (path, repo) = temp_repo("config");
+        let mut config = repo.config().unwrap();
+
+        config
+            .set("git-together.authors.jh", "James Holden; jholden")
+            .unwrap();
+        config
+            .set("git-together.authors.nn", "Naomi Nagata; nnagata")
+            .unwrap();
+        assert_eq!(config.get("git-together.authors.jh").unwrap(),
+                   "James Holden; jholden");
+
+        let authors = config.get_all("git-together.authors.").unwrap();
+        assert_eq!(authors.len(), 2);
+        assert_eq!(authors["git-together.authors.nn"],
+                   "Naomi Nagata; nnagata");
+
+        assert!(config.exists("git-together.authors.nn").unwrap());
+        config.unset("git-together.authors.nn").unwrap();
+        assert!(config.get("git-together.authors.nn").is_err());
+        assert!(!config.exists("git-together.authors.nn").unwrap());
+        assert_eq!(config.get_all("git-together.authors.").unwrap().len(), 1);
+
+        fs::remove_dir_all(path).unwrap();
+    }
+
+    #[test]
+    fn schema_version() {
+        let (path, repo) = temp_repo("schema-version");
+        let mut config = repo.config().unwrap();
+        config.set("user.name", "James Holden").unwrap();
+        assert!(!config.exists("git-together.schema-version").unwrap());
+
+        config.set("git-together.active", "jh").unwrap();
+        assert_eq!(config.get("git-together.schema-version").unwrap(),
+                   ::SCHEMA_VERSION.to_string());
+
+        config.set("git-together.schema-version", "0").unwrap();
+        config.add("git-together.aliases.holden", "jh").unwrap();
+        assert_eq!(config.get("git-together.schema-version").unwrap(), "0");
+
+        fs::remove_dir_all(path).unwrap();
+    }
+
+    #[test]
+    fn schema_version_with_includes() {
+        let (path, repo) = temp_repo("schema-version-includes");
+        let shared = path.join("shared-authors");
+        fs::write(&shared,
+                  "[git-together \"authors\"]\n\
+                   \tjh = \"James Holden; jholden\"\n")
+            .unwrap();
+        {
+            let mut local = repo.local_config().unwrap();
+            local
+                .set_str("include.path", shared.to_str().unwrap())
+                .unwrap();
+            local.set_str("git-together.schema-version", "2").unwrap();
+        }
+
+        // Opened again, as a new process would, since the writes above leave
+        // this repo's own config handle stale.
+        let repo = Repo::open(&path).unwrap();
+        let mut config = repo.config().unwrap();
+        assert_eq!(config.get("git-together.schema-version").unwrap(), "2");
+        config.set("git-together.active", "jh").unwrap();
+        config.add("git-together.aliases.holden", "jh").unwrap();
+        assert_eq!(config.get("git-together.schema-version").unwrap(), "2");
+
+        // A version in an included file counts too.
+        config.unset("git-together.schema-version").unwrap();
+        fs::write(&shared, "[git-together]\n\tschema-version = 2\n").unwrap();
+        let repo = Repo::open(&path).unwrap();
+        let mut config = repo.config().unwrap();
+        config.set("git-together.active", "jh").unwrap();
+        assert_eq!(config.get("git-together.schema-version").unwrap(), "2");
+        assert!(!fs::read_to_string(path.join(".git/config"))
+                     .unwrap()
+                     .contains("schema-version ="));
+
+        fs::remove_dir_all(path).unwrap();
+    }
+
+    #[test]
+    fn includes() {
+        let (path, repo) = temp_repo("includes");
+        let shared = path.join("shared-authors");
+        fs::write(&shared,
+                  "[git-together]\n\
+                   \tdomain = rocinante.com\n\
+                   [git-together \"authors\"]\n\
+                   \tjh = \"James Holden; jholden\"\n\
+                   \tnn = \"Naomi Nagata; nnagata\"\n")
+            .unwrap();
+        repo.local_config()
+            .unwrap()
+            .set_str("include.path", shared.to_str().unwrap())
+            .unwrap();
+
+        let mut config = repo.config().unwrap();
+        assert_eq!(config.get("git-together.domain").unwrap(), "rocinante.com");
+        assert_eq!(config.get("git-together.authors.jh").unwrap(),
+                   "James Holden; jholden");
+        assert_eq!(config.get_all("git-together.authors.").unwrap().len(), 2);
+
+        config
+            .set("git-together.authors.ak", "Alex Kamal; akamal")
+            .unwrap();
+        let _ = config.unset("git-together.authors.nn");
+        assert_eq!(config.get("git-together.authors.nn").unwrap(),
+                   "Naomi Nagata; nnagata");
+        assert!(fs::read_to_string(&shared).unwrap().contains("Naomi Nagata"));
+        assert!(!fs::read_to_string(&shared).unwrap().contains("ak"));
+        assert!(fs::read_to_string(path.join(".git/config"))
+                    .unwrap()
+                    .contains("Alex Kamal"));
+        assert_eq!(config.get("git-together.authors.jh").unwrap(),
+                   "James Holden; jholden");
+        assert_eq!(config.get_all("git-together.authors.").unwrap().len(), 3);
+
+        fs::remove_dir_all(path).unwrap();
+    }
+
+    #[test]
+    fn bare() {
+        let path = env::temp_dir().join(format!("git-together-bare-{}", process::id()));
+        let _ = fs::remove_dir_all(&path);
+        let repo = Repo { repo: git2::Repository::init_bare(&path).unwrap() };
+        fs::write(path.join(".git-together"),
+                  "[git-together]\n\tdomain = rocinante.com\n")
+            .unwrap();
+        assert!(repo.is_bare());
+
+        repo.auto_include(".git-together").unwrap();
+        assert!(repo.include_paths().unwrap().is_empty());
+        assert_eq!(repo.current_branch(), None);
+        assert_eq!(repo.head_id(), None);
+
+        let mut config = repo.config().unwrap();
+        config.set("git-together.active", "jh+nn").unwrap();
+        assert_eq!(config.get("git-together.active").unwrap(), "jh+nn");
+        assert!(fs::read_to_string(path.join("config"))
+                    .unwrap()
+                    .contains("jh+nn"));
+
+        fs::remove_dir_all(path).unwrap();
+    }
+
+    #[test]
+    fn head_id() {
+        let (path, repo) = temp_repo("head-id");
+        assert_eq!(repo.head_id(), None);
+
+        let signature = git2::Signature::now("James Holden", "jholden@rocinante.com").unwrap();
+        let tree = {
+            let mut index = repo.repo.index().unwrap();
+            let id = index.write_tree().unwrap();
+            repo.repo.find_tree(id).unwrap()
+        };
+        let id = repo.repo
+            .commit(Some("HEAD"), &signature, &signature, "Fix the drive", &tree, &[])
+            .unwrap();
+        assert_eq!(repo.head_id(), Some(id.to_string()));
+        assert_eq!(repo.head_parent_id(), None);
+        assert_eq!(repo.current_branch(), Some("master".into()));
+
+        let parent = repo.repo.find_commit(id).unwrap();
+        repo.repo
+            .commit(Some("HEAD"), &signature, &signature, "Fix it again", &tree, &[&parent])
+            .unwrap();
+        assert_eq!(repo.head_parent_id(), Some(id.to_string()));
+
+        fs::remove_dir_all(path).unwrap();
+    }
+
+    #[test]
+    fn decode_non_utf8() {
+        assert_eq!(decode("git-together.authors.jh", b"James Holden; jholden", false).unwrap(),
+                   "James Holden; jholden");
+
+        let bytes = b"Jos\xe9 Holden; jholden";
+        match decode("git-together.authors.jh", bytes, false) {
+            Err(Error(ErrorKind::NonUtf8Value(ref name), _)) => {
+                assert_eq!(name, "git-together.authors.jh")
+            }
+            result => panic!("unexpected result: {:?}", result),
+        }
+        assert_eq!(decode("git-together.authors.jh", bytes, true).unwrap(),
+                   "Jos\u{fffd} Holden; jholden");
     }
 }
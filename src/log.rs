@@ -0,0 +1,84 @@
+use std::fmt;
+
+/// A completed pairing session, as recorded in the pairing log.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LogEntry {
+    pub started_at: u64,
+    pub duration: u64,
+    pub inits: Vec<String>,
+}
+
+impl LogEntry {
+    pub fn parse(line: &str) -> Option<LogEntry> {
+        let mut split = line.split_whitespace();
+        let started_at = split.next().and_then(|s| s.parse().ok());
+        let duration = split.next().and_then(|s| s.parse().ok());
+        let inits = split.next();
+        match (started_at, duration, inits, split.next()) {
+            (Some(started_at), Some(duration), Some(inits), None) => {
+                Some(LogEntry {
+                         started_at: started_at,
+                         duration: duration,
+                         inits: inits.split('+').map(String::from).collect(),
+                     })
+            }
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for LogEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f,
+               "{} {} {}",
+               self.started_at,
+               self.duration,
+               self.inits.join("+"))
+    }
+}
+
+/// Which entries `purge_log` keeps.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LogRetention {
+    /// The last `n` entries.
+    LastN(usize),
+    /// Entries that started at or after this time, in seconds since the
+    /// epoch.
+    Since(u64),
+}
+
+/// Parses the raw log value, one entry per line, skipping malformed lines.
+pub fn parse(raw: &str) -> Vec<LogEntry> {
+    raw.lines().filter_map(LogEntry::parse).collect()
+}
+
+pub fn serialize(entries: &[LogEntry]) -> String {
+    entries
+        .iter()
+        .map(|entry| entry.to_string())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let raw = "1000 3600 jh+nn\nnot an entry\n5000 60 ca";
+        let entries = parse(raw);
+        assert_eq!(entries,
+                   vec![LogEntry {
+                            started_at: 1000,
+                            duration: 3600,
+                            inits: vec!["jh".into(), "nn".into()],
+                        },
+                        LogEntry {
+                            started_at: 5000,
+                            duration: 60,
+                            inits: vec!["ca".into()],
+                        }]);
+        assert_eq!(serialize(&entries), "1000 3600 jh+nn\n5000 60 ca");
+    }
+}
@@ -0,0 +1,91 @@
+//! MD5 (RFC 1321), which Gravatar uses to hash emails. Not for anything that
+//! needs to be secure.
+
+const SHIFTS: [u32; 64] = [7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 5, 9, 14,
+                           20, 5, 9, 14, 20, 5, 9, 14, 20, 5, 9, 14, 20, 4, 11, 16, 23, 4, 11,
+                           16, 23, 4, 11, 16, 23, 4, 11, 16, 23, 6, 10, 15, 21, 6, 10, 15, 21, 6,
+                           10, 15, 21, 6, 10, 15, 21];
+
+pub fn hex_digest(input: &[u8]) -> String {
+    digest(input)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+pub fn digest(input: &[u8]) -> [u8; 16] {
+    let constants: Vec<u32> = (0..64)
+        .map(|i| ((i as f64 + 1.0).sin().abs() * 4294967296.0) as u32)
+        .collect();
+
+    let mut message = input.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    let bits = (input.len() as u64).wrapping_mul(8);
+    for i in 0..8 {
+        message.push((bits >> (8 * i)) as u8);
+    }
+
+    let mut state: [u32; 4] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476];
+    for chunk in message.chunks(64) {
+        let words: Vec<u32> = chunk
+            .chunks(4)
+            .map(|b| {
+                     u32::from(b[0]) | u32::from(b[1]) << 8 | u32::from(b[2]) << 16 |
+                     u32::from(b[3]) << 24
+                 })
+            .collect();
+
+        let (mut a, mut b, mut c, mut d) = (state[0], state[1], state[2], state[3]);
+        for i in 0..64 {
+            let (f, g) = match i / 16 {
+                0 => ((b & c) | (!b & d), i),
+                1 => ((d & b) | (!d & c), (5 * i + 1) % 16),
+                2 => (b ^ c ^ d, (3 * i + 5) % 16),
+                _ => (c ^ (b | !d), (7 * i) % 16),
+            };
+            let rotated = a.wrapping_add(f)
+                .wrapping_add(constants[i])
+                .wrapping_add(words[g])
+                .rotate_left(SHIFTS[i]);
+            a = d;
+            d = c;
+            c = b;
+            b = b.wrapping_add(rotated);
+        }
+
+        state[0] = state[0].wrapping_add(a);
+        state[1] = state[1].wrapping_add(b);
+        state[2] = state[2].wrapping_add(c);
+        state[3] = state[3].wrapping_add(d);
+    }
+
+    let mut digest = [0; 16];
+    for (i, word) in state.iter().enumerate() {
+        for j in 0..4 {
+            digest[4 * i + j] = (word >> (8 * j)) as u8;
+        }
+    }
+    digest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rfc_1321() {
+        let vectors = [("", "d41d8cd98f00b204e9800998ecf8427e"),
+                       ("a", "0cc175b9c0f1b6a831c399e269772661"),
+                       ("abc", "900150983cd24fb0d6963f7d28e17f72"),
+                       ("message digest", "f96b697d7cb7938d525a2f31aaf161d0"),
+                       ("abcdefghijklmnopqrstuvwxyz", "c3fcd3d76192e4007dfb496cca67e13b"),
+                       ("12345678901234567890123456789012345678901234567890123456789012345678901234567890",
+                        "57edf4a22be3c955ac49da2e2107b67a")];
+        for &(input, expected) in &vectors {
+            assert_eq!(hex_digest(input.as_bytes()), expected);
+        }
+    }
+}
@@ -0,0 +1,406 @@
+use std::env;
+use std::fs::File;
+use std::io::Read;
+use std::path::PathBuf;
+
+use errors::*;
+
+/// Formats the Unix time `at` in `tz` the way git expects dates in
+/// `GIT_AUTHOR_DATE` and `GIT_COMMITTER_DATE`, e.g. `1500000000 +0200`.
+pub fn git_date(tz: &str, at: i64) -> Result<String> {
+    let offset = offset_at(tz, at)?;
+    let sign = if offset < 0 { '-' } else { '+' };
+    let minutes = offset.abs() / 60;
+    Ok(format!("{} {}{:02}{:02}", at, sign, minutes / 60, minutes % 60))
+}
+
+/// The UTC offset in seconds of `tz` at the Unix time `at`. `tz` is either a
+/// fixed offset (`+0200`, `-05:30`, `UTC`) or an IANA name like
+/// `America/New_York`, which is looked up in the system zoneinfo database
+/// (`$TZDIR` or `/usr/share/zoneinfo`).
+pub fn offset_at(tz: &str, at: i64) -> Result<i32> {
+    if let Some(offset) = parse_fixed(tz) {
+        return Ok(offset);
+    }
+
+    let valid_name = !tz.is_empty() && !tz.starts_with('/') && !tz.contains("..") &&
+                     tz.chars()
+                         .all(|c| c.is_ascii_alphanumeric() || "/_+-".contains(c));
+    if !valid_name {
+        return Err(format!("invalid timezone: '{}'", tz).into());
+    }
+
+    let mut path = env::var("TZDIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from("/usr/share/zoneinfo"));
+    path.push(tz);
+
+    let mut data = Vec::new();
+    File::open(&path)
+        .and_then(|mut file| file.read_to_end(&mut data))
+        .chain_err(|| format!("unknown timezone: '{}'", tz))?;
+    parse_tzif(&data, at).ok_or_else(|| format!("invalid zoneinfo for timezone '{}'", tz).into())
+}
+
+fn parse_fixed(tz: &str) -> Option<i32> {
+    match tz {
+        "UTC" | "GMT" | "Z" => return Some(0),
+        _ => {}
+    }
+
+    let sign = match tz.chars().next() {
+        Some('+') => 1,
+        Some('-') => -1,
+        _ => return None,
+    };
+    // The lengths below are in bytes, so anything else can't be sliced.
+    let rest = &tz[1..];
+    if !rest.is_ascii() {
+        return None;
+    }
+    let (hours, minutes) = match (rest.len(), rest.find(':')) {
+        (2, None) => (rest, "0"),
+        (4, None) => (&rest[..2], &rest[2..]),
+        (5, Some(2)) => (&rest[..2], &rest[3..]),
+        _ => return None,
+    };
+    if !hours.chars().chain(minutes.chars()).all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    let hours: i32 = hours.parse().ok()?;
+    let minutes: i32 = minutes.parse().ok()?;
+    if hours > 14 || minutes > 59 {
+        return None;
+    }
+    Some(sign * (hours * 3600 + minutes * 60))
+}
+
+struct Bytes<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Bytes<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        let bytes = self.data.get(self.pos..self.pos + len)?;
+        self.pos += len;
+        Some(bytes)
+    }
+
+    fn int(&mut self, len: usize) -> Option<i64> {
+        let bytes = self.take(len)?;
+        let unsigned = bytes.iter().fold(0u64, |n, &b| (n << 8) | u64::from(b));
+        let shift = 64 - 8 * len as u32;
+        Some(((unsigned << shift) as i64) >> shift)
+    }
+}
+
+struct Counts {
+    isut: usize,
+    isstd: usize,
+    leap: usize,
+    time: usize,
+    types: usize,
+    chars: usize,
+}
+
+fn read_header(bytes: &mut Bytes) -> Option<(u8, Counts)> {
+    if bytes.take(4)? != b"TZif" {
+        return None;
+    }
+    let version = bytes.take(1)?[0];
+    bytes.take(15)?;
+
+    let mut counts = [0; 6];
+    for count in &mut counts {
+        *count = bytes.int(4)? as usize;
+    }
+    Some((version,
+          Counts {
+              isut: counts[0],
+              isstd: counts[1],
+              leap: counts[2],
+              time: counts[3],
+              types: counts[4],
+              chars: counts[5],
+          }))
+}
+
+// See RFC 8536. Version 2+ files repeat the data with 64-bit times and end
+// with a POSIX TZ string for times after the last transition.
+fn parse_tzif(data: &[u8], at: i64) -> Option<i32> {
+    let mut bytes = Bytes { data: data, pos: 0 };
+    let (version, mut counts) = read_header(&mut bytes)?;
+    let mut time_size = 4;
+
+    if version >= b'2' {
+        bytes.take(counts.time * 5 + counts.types * 6 + counts.chars + counts.leap * 8 +
+                   counts.isstd + counts.isut)?;
+        counts = read_header(&mut bytes)?.1;
+        time_size = 8;
+    }
+
+    let mut times = Vec::new();
+    for _ in 0..counts.time {
+        times.push(bytes.int(time_size)?);
+    }
+    let indices = bytes.take(counts.time)?.to_vec();
+    let mut offsets = Vec::new();
+    for _ in 0..counts.types {
+        offsets.push(bytes.int(4)? as i32);
+        bytes.take(2)?;
+    }
+    bytes.take(counts.chars + counts.leap * (time_size + 4) + counts.isstd + counts.isut)?;
+
+    match times.iter().rposition(|&time| time <= at) {
+        Some(i) if i + 1 == times.len() && time_size == 8 => {
+            let footer = String::from_utf8_lossy(&data[bytes.pos..]);
+            let footer = footer.trim();
+            if footer.is_empty() {
+                offsets.get(usize::from(indices[i])).cloned()
+            } else {
+                posix_offset_at(footer, at)
+            }
+        }
+        Some(i) => offsets.get(usize::from(indices[i])).cloned(),
+        None if times.is_empty() && time_size == 8 && bytes.pos < data.len() => {
+            let footer = String::from_utf8_lossy(&data[bytes.pos..]);
+            posix_offset_at(footer.trim(), at).or_else(|| offsets.first().cloned())
+        }
+        None => offsets.first().cloned(),
+    }
+}
+
+struct Rule {
+    month: u32,
+    week: u32,
+    weekday: u32,
+    time: i64,
+}
+
+// The offset for a POSIX TZ string like `EST5EDT,M3.2.0,M11.1.0`. Only the
+// `Mm.w.d` rule form is supported, which is what tzdata uses.
+fn posix_offset_at(tz: &str, at: i64) -> Option<i32> {
+    let mut rest = tz;
+    skip_name(&mut rest)?;
+    let std_offset = -parse_posix_time(&mut rest)?;
+    if rest.is_empty() {
+        return Some(std_offset as i32);
+    }
+
+    skip_name(&mut rest)?;
+    let dst_offset = if rest.starts_with(',') {
+        std_offset + 3600
+    } else {
+        -parse_posix_time(&mut rest)?
+    };
+
+    let mut rules = rest.split(',').skip(1).map(parse_rule);
+    let start = rules.next()??;
+    let end = rules.next()??;
+
+    let (year, _, _) = civil_from_days((at + std_offset).div_euclid(86400));
+    let start_at = rule_day(year, &start) * 86400 + start.time - std_offset;
+    let end_at = rule_day(year, &end) * 86400 + end.time - dst_offset;
+    let dst = if start_at < end_at {
+        start_at <= at && at < end_at
+    } else {
+        !(end_at <= at && at < start_at)
+    };
+
+    Some(if dst { dst_offset } else { std_offset } as i32)
+}
+
+fn skip_name(rest: &mut &str) -> Option<()> {
+    let len = if rest.starts_with('<') {
+        rest.find('>')? + 1
+    } else {
+        rest.find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(rest.len())
+    };
+    if len < 3 {
+        return None;
+    }
+    *rest = &rest[len..];
+    Some(())
+}
+
+// `[+-]hh[:mm[:ss]]`, in seconds.
+fn parse_posix_time(rest: &mut &str) -> Option<i64> {
+    let len = rest.find(|c: char| !(c.is_ascii_digit() || "+-:".contains(c)))
+        .unwrap_or(rest.len());
+    let (time, remaining) = rest.split_at(len);
+    *rest = remaining;
+
+    let (sign, time) = if time.starts_with('-') {
+        (-1, &time[1..])
+    } else {
+        (1, time.trim_start_matches('+'))
+    };
+    let mut seconds = 0;
+    let mut unit = 3600;
+    for part in time.split(':') {
+        seconds += part.parse::<i64>().ok()? * unit;
+        unit /= 60;
+    }
+    Some(sign * seconds)
+}
+
+fn parse_rule(rule: &str) -> Option<Rule> {
+    let mut split = rule.splitn(2, '/');
+    let date = split.next()?;
+    let time = match split.next() {
+        Some(mut time) => parse_posix_time(&mut time)?,
+        None => 2 * 3600,
+    };
+
+    if !date.starts_with('M') {
+        return None;
+    }
+    let fields = date[1..]
+        .split('.')
+        .map(|field| field.parse().ok())
+        .collect::<Option<Vec<u32>>>()?;
+    match *fields.as_slice() {
+        [month, week, weekday] if month >= 1 && month <= 12 && week >= 1 && week <= 5 &&
+                                  weekday <= 6 => {
+            Some(Rule {
+                     month: month,
+                     week: week,
+                     weekday: weekday,
+                     time: time,
+                 })
+        }
+        _ => None,
+    }
+}
+
+// Days since the epoch of the `week`th `weekday` of the rule's month, where
+// week 5 means the last one.
+fn rule_day(year: i64, rule: &Rule) -> i64 {
+    let first = days_from_civil(year, rule.month, 1);
+    let first_weekday = (first + 4).rem_euclid(7) as u32;
+    let mut day = 1 + (rule.weekday + 7 - first_weekday) % 7 + (rule.week - 1) * 7;
+
+    let (next_year, next_month) = if rule.month == 12 {
+        (year + 1, 1)
+    } else {
+        (year, rule.month + 1)
+    };
+    let days_in_month = (days_from_civil(next_year, next_month, 1) - first) as u32;
+    while day > days_in_month {
+        day -= 7;
+    }
+
+    first + i64::from(day) - 1
+}
+
+// Howard Hinnant's `days_from_civil` and `civil_from_days`.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let month = i64::from(month);
+    let doy = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + i64::from(day) -
+              1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let doe = days - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 2024-01-15T12:00:00Z and 2024-07-15T12:00:00Z
+    const WINTER: i64 = 1705320000;
+    const SUMMER: i64 = 1721044800;
+
+    #[test]
+    fn fixed_offsets() {
+        assert_eq!(offset_at("UTC", WINTER).unwrap(), 0);
+        assert_eq!(offset_at("+0200", WINTER).unwrap(), 7200);
+        assert_eq!(offset_at("-05:30", WINTER).unwrap(), -19800);
+        assert_eq!(offset_at("+09", WINTER).unwrap(), 32400);
+        assert_eq!(git_date("-0330", 1500000000).unwrap(), "1500000000 -0330");
+        assert_eq!(git_date("UTC", 1500000000).unwrap(), "1500000000 +0000");
+
+        assert!(offset_at("+2500", WINTER).is_err());
+        assert!(offset_at("", WINTER).is_err());
+        assert!(offset_at("../etc/passwd", WINTER).is_err());
+        assert!(offset_at("Not/A_Zone", WINTER).is_err());
+
+        assert_eq!(parse_fixed("+1é1"), None);
+        assert_eq!(parse_fixed("-é:00"), None);
+        assert!(offset_at("+1é1", WINTER).is_err());
+    }
+
+    #[test]
+    fn posix_rules() {
+        let new_york = "EST5EDT,M3.2.0,M11.1.0";
+        assert_eq!(posix_offset_at(new_york, WINTER), Some(-18000));
+        assert_eq!(posix_offset_at(new_york, SUMMER), Some(-14400));
+        // DST started at 2024-03-10T07:00:00Z.
+        assert_eq!(posix_offset_at(new_york, 1710054000 - 1), Some(-18000));
+        assert_eq!(posix_offset_at(new_york, 1710054000), Some(-14400));
+
+        let sydney = "AEST-10AEDT,M10.1.0,M4.1.0/3";
+        assert_eq!(posix_offset_at(sydney, WINTER), Some(39600));
+        assert_eq!(posix_offset_at(sydney, SUMMER), Some(36000));
+
+        assert_eq!(posix_offset_at("<+0530>-5:30", SUMMER), Some(19800));
+        assert_eq!(posix_offset_at("nonsense", SUMMER), None);
+    }
+
+    #[test]
+    fn tzif() {
+        fn header(version: u8, time: u32, types: u32, chars: u32) -> Vec<u8> {
+            let mut header = b"TZif".to_vec();
+            header.push(version);
+            header.extend(&[0; 15]);
+            for &count in &[0, 0, 0, time, types, chars] {
+                header.extend(&[(count >> 24) as u8, (count >> 16) as u8, (count >> 8) as u8,
+                                count as u8]);
+            }
+            header
+        }
+
+        // Berlin-ish: a single 64-bit transition to CET at 2000-01-01, then
+        // the POSIX rule for everything after it.
+        let mut data = header(b'2', 0, 1, 4);
+        data.extend(&[0, 0, 0x0e, 0x10, 0, 0]);
+        data.extend(b"CET\0");
+        data.extend(header(b'2', 1, 1, 4));
+        data.extend(&[0, 0, 0, 0, 0x38, 0x6d, 0x43, 0x80]);
+        data.push(0);
+        data.extend(&[0, 0, 0x0e, 0x10, 0, 0]);
+        data.extend(b"CET\0");
+        data.extend(b"\nCET-1CEST,M3.5.0,M10.5.0/3\n");
+
+        assert_eq!(parse_tzif(&data, 0), Some(3600));
+        assert_eq!(parse_tzif(&data, WINTER), Some(3600));
+        assert_eq!(parse_tzif(&data, SUMMER), Some(7200));
+        assert_eq!(parse_tzif(b"not a tzif file", SUMMER), None);
+    }
+
+    #[test]
+    fn civil_days() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(days_from_civil(2024, 3, 10), 19792);
+        assert_eq!(civil_from_days(19792), (2024, 3, 10));
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+    }
+}
@@ -0,0 +1,67 @@
+/// The git subcommands that `signoff` knows how to attribute.
+///
+/// | verb          | author env | committer env | `--signoff` |
+/// |---------------|------------|---------------|-------------|
+/// | `commit`      | yes        | yes           | yes         |
+/// | `revert`      | yes        | yes           | yes         |
+/// | `merge`       | yes        | no            | no          |
+/// | `cherry-pick` | no         | yes           | yes         |
+/// | `tag`         | yes        | tagger        | no          |
+///
+/// The committer env and `--signoff` are only ever added for a pair whose
+/// committer isn't also the author, and `--signoff` never when `-s` or
+/// `--signoff` is already on the command. `cherry-pick` keeps the original
+/// commit's author, so only the committer is set. A merge's committer is
+/// whoever integrates it, so that's left to git, and the rest of the pair
+/// is credited as co-authors instead. git takes a tag's tagger from the
+/// committer env, which for a tag is the pair's author, since a tag has no
+/// committer of its own.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Verb {
+    Commit,
+    Merge,
+    Revert,
+    CherryPick,
+    Tag,
+}
+
+impl Verb {
+    pub fn from_subcommand(sub_cmd: &str) -> Option<Verb> {
+        match sub_cmd {
+            "commit" => Some(Verb::Commit),
+            "merge" => Some(Verb::Merge),
+            "revert" => Some(Verb::Revert),
+            "cherry-pick" => Some(Verb::CherryPick),
+            "tag" => Some(Verb::Tag),
+            _ => None,
+        }
+    }
+
+    pub fn sets_author(&self) -> bool {
+        *self != Verb::CherryPick
+    }
+
+    pub fn sets_committer(&self) -> bool {
+        *self != Verb::Merge && *self != Verb::Tag
+    }
+
+    pub fn takes_signoff(&self) -> bool {
+        *self != Verb::Merge && *self != Verb::Tag
+    }
+
+    /// `git merge` and `git tag` have no `--trailer`, but a message given with
+    /// `-m` can take the co-authors as another `-m` paragraph.
+    pub fn takes_message_trailers(&self) -> bool {
+        *self == Verb::Merge || *self == Verb::Tag
+    }
+
+    /// Tagging doesn't make a commit, so it doesn't rotate the pair.
+    pub fn makes_commit(&self) -> bool {
+        *self != Verb::Tag
+    }
+
+    /// Only `git commit` accepts `--trailer`.
+    pub fn takes_trailers(&self) -> bool {
+        *self == Verb::Commit
+    }
+}